@@ -0,0 +1,24 @@
+use thiserror::Error;
+
+use super::models::WsChannel;
+
+#[derive(Debug, Error)]
+pub enum WsError {
+    #[error("socket is not authenticated")]
+    SocketNotAuthenticated,
+    #[error("not subscribed to channel {0:?}")]
+    NotSubscribedToThisChannel(WsChannel),
+    #[error("did not receive a subscription confirmation in time")]
+    MissingSubscriptionConfirmation,
+    #[error("the websocket connection actor is no longer running")]
+    ActorShutDown,
+    #[error("transport error: {0}")]
+    Transport(String),
+    #[cfg(feature = "native")]
+    #[error(transparent)]
+    Tungstenite(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    SystemTime(#[from] std::time::SystemTimeError),
+}
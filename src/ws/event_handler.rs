@@ -0,0 +1,5 @@
+use super::models::{EventData, Symbol};
+
+pub trait EventHandler {
+    fn on_event(&self, event: EventData, symbol: Option<Symbol>);
+}
@@ -1,30 +1,63 @@
-use futures::{
-    ready,
-    task::{Context, Poll},
-    Future, SinkExt, Stream, StreamExt,
-};
+use futures::{task::{Context, Poll}, Future, Stream, StreamExt};
 use hmac_sha256::HMAC;
 use serde_json::json;
-use std::{collections::VecDeque, sync::Arc};
+use std::collections::HashMap;
 use std::pin::Pin;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::time::Interval;
-use tokio::{net::TcpStream, time};
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::wrappers::BroadcastStream;
 
 use crate::ws::WsMessageType;
 
 use super::error::*;
 use super::event_handler::*;
 use super::models::*;
+use super::orderbook::OrderBook;
+use super::rt;
+use super::timer::{self, PingTimer};
+use super::transport::{self, EndpointConnector, WsConnector, WsTransport};
+
+/// Initial delay before the first reconnect attempt, doubled after each failure up to
+/// [`MAX_RECONNECT_BACKOFF`].
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+/// How long a `subscribe`/`unsubscribe` call waits for the matching ack before giving up.
+const SUBSCRIPTION_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+type ConfirmationSender = oneshot::Sender<Result<(), WsError>>;
+
+enum Command {
+    Subscribe(WsChannel, ConfirmationSender),
+    Unsubscribe(WsChannel, ConfirmationSender),
+}
+
+/// Capacity of the broadcast channel every decoded event is published on. Generous
+/// headroom for a slow consumer before it starts missing (`Lagged`) messages.
+const EVENT_BROADCAST_CAPACITY: usize = 1024;
 
+/// Handle to a background connection actor that owns the `WebSocketStream`. Cloneable
+/// parts of the API (subscribe/unsubscribe) talk to the actor over an `mpsc` channel.
+/// Decoded events are published once, on a `broadcast` channel, so any number of
+/// independent consumers can read the same upstream socket via `subscribe_events`
+/// without each opening their own FTX connection.
 pub struct FtxWebsocket {
     channels: Vec<WsChannel>,
-    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
-    buf: VecDeque<(Option<Symbol>, EventData)>,
-    ping_timer: Interval,
     is_authenticated: bool,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+    data_tx: broadcast::Sender<(Option<Symbol>, EventData)>,
+    data_rx: BroadcastStream<(Option<Symbol>, EventData)>,
+    // `broadcast::Sender` only reports a receiver closed once every clone of it is
+    // dropped, and `data_tx` above keeps one alive for as long as this handle does. So
+    // when the actor exits (e.g. the connection drops and `auto_reconnect` is off) it
+    // drops its own `data_tx` clone, but ours keeps the channel open forever and
+    // `data_rx` would just pend. This oneshot is the actor's side-channel signal that
+    // it's gone: its `Sender` half lives on `WsActor` and is dropped when `run` returns,
+    // which resolves this `Receiver` and lets `poll_next` end the stream instead of
+    // hanging.
+    shutdown_rx: oneshot::Receiver<()>,
     event_handler: Option<Arc<dyn EventHandler + Send + Sync + 'static>>,
+    order_books: Arc<Mutex<HashMap<Symbol, OrderBook>>>,
 }
 
 impl FtxWebsocket {
@@ -35,61 +68,113 @@ impl FtxWebsocket {
         secret: Option<String>,
         subaccount: Option<String>,
     ) -> Result<Self, WsError> {
-        let (mut stream, _) = connect_async(FtxWebsocket::ENDPOINT).await?;
+        Self::connect_with_options(key, secret, subaccount, false, false).await
+    }
 
-        let is_authenticated = if let (Some(key), Some(secret)) = (key, secret) {
-            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
-            let sign_payload = format!("{}websocket_login", timestamp);
-            let sign = HMAC::mac(sign_payload.as_bytes(), secret.as_bytes());
-            let sign = hex::encode(sign);
+    /// Like [`FtxWebsocket::connect`], but with `auto_reconnect` controlling whether the
+    /// connection actor transparently re-establishes itself (re-running the login and
+    /// resubscribing to every previously-subscribed channel) when the underlying
+    /// connection drops, instead of winding the actor down, and `track_order_books`
+    /// controlling whether the actor also maintains a local, checksum-verified
+    /// [`OrderBook`] per market (see [`FtxWebsocket::best_bid_ask`] and
+    /// [`FtxWebsocket::order_book_depth`]) instead of only handing consumers raw diffs.
+    pub async fn connect_with_options(
+        key: Option<String>,
+        secret: Option<String>,
+        subaccount: Option<String>,
+        auto_reconnect: bool,
+        track_order_books: bool,
+    ) -> Result<Self, WsError> {
+        let mut stream = transport::connect(FtxWebsocket::ENDPOINT).await?;
 
-            stream
-                .send(Message::Text(
-                    json!({
-                        "op": "login",
-                        "args": {
-                            "key": key,
-                            "sign": sign,
-                            "time": timestamp as u64,
-                            "subaccount": subaccount,
-                        }
-                    })
-                    .to_string(),
-                ))
-                .await?;
+        let is_authenticated = if let (Some(key), Some(secret)) = (&key, &secret) {
+            login(&mut stream, key, secret, subaccount.clone()).await?;
             true
         } else {
             false
         };
 
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (data_tx, data_rx) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let order_books = Arc::new(Mutex::new(HashMap::new()));
+
+        let actor = WsActor {
+            connector: EndpointConnector(FtxWebsocket::ENDPOINT),
+            stream,
+            ping_timer: PingTimer::new(Duration::from_secs(15)),
+            key,
+            secret,
+            subaccount,
+            auto_reconnect,
+            track_order_books,
+            channels: Vec::new(),
+            cmd_rx,
+            data_tx: data_tx.clone(),
+            pending: HashMap::new(),
+            order_books: order_books.clone(),
+            _shutdown_tx: shutdown_tx,
+        };
+        rt::spawn(actor.run());
+
         Ok(Self {
             channels: Vec::new(),
-            stream,
-            buf: VecDeque::new(),
-            ping_timer: time::interval(Duration::from_secs(15)),
             is_authenticated,
+            cmd_tx,
+            data_rx: BroadcastStream::new(data_rx),
+            data_tx,
+            shutdown_rx,
             event_handler: None,
+            order_books,
         })
     }
 
-    pub fn add_event_handler<H>(&mut self, handler: Arc<dyn EventHandler + Send + Sync + 'static>)
-    {
+    pub fn add_event_handler<H>(&mut self, handler: Arc<dyn EventHandler + Send + Sync + 'static>) {
         self.event_handler = Some(handler);
     }
 
-    async fn ping(&mut self) -> Result<(), WsError> {
-        self.stream
-            .send(Message::Text(
-                json!({
-                    "op": "ping",
-                })
-                .to_string(),
-            ))
-            .await?;
+    /// Returns an independent receiver of every event published on this socket. Each
+    /// caller gets its own handle and can filter client-side by `Symbol`/`EventData`
+    /// variant; the upstream connection is read once no matter how many subscribers
+    /// there are.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<(Option<Symbol>, EventData)> {
+        self.data_tx.subscribe()
+    }
 
-        Ok(())
+    /// Best bid/ask on the locally-maintained order book for `symbol`. Returns `None`
+    /// until `track_order_books` is enabled and a snapshot has been received.
+    pub fn best_bid_ask(&self, symbol: &str) -> Option<(Option<(f64, f64)>, Option<(f64, f64)>)> {
+        self.order_books
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .map(OrderBook::best_bid_ask)
     }
 
+    /// Up to `depth` price levels on each side of the locally-maintained order book for
+    /// `symbol`, bids highest-first and asks lowest-first. Returns `None` until
+    /// `track_order_books` is enabled and a snapshot has been received.
+    pub fn order_book_depth(
+        &self,
+        symbol: &str,
+        depth: usize,
+    ) -> Option<(Vec<(f64, f64)>, Vec<(f64, f64)>)> {
+        self.order_books
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .map(|book| book.depth(depth))
+    }
+
+    /// Subscribes to every channel in `channels`. The commands are all sent to the
+    /// actor up front and their confirmations awaited together, so N channels cost one
+    /// round trip's worth of latency rather than N sequential ones. Each channel's
+    /// outcome is tracked independently: channels that were actually acked are recorded
+    /// even if another channel in the same batch timed out or failed, so the handle
+    /// never forgets a channel the server did confirm. The actor's own replay list is
+    /// not pruned the same way: a channel whose confirmation failed is still
+    /// resubscribed on reconnect. On partial failure the first error encountered is
+    /// returned.
     pub async fn subscribe(&mut self, channels: &[WsChannel]) -> Result<(), WsError> {
         for channel in channels.iter() {
             if (channel == &WsChannel::Fills || channel == &WsChannel::Orders)
@@ -97,14 +182,34 @@ impl FtxWebsocket {
             {
                 return Err(WsError::SocketNotAuthenticated);
             }
-            self.channels.push(channel.clone());
         }
 
-        self.subscribe_or_unsubscribe(channels, true).await?;
+        let channels = channels.to_vec();
+        let outcomes = self
+            .send_commands(channels.iter().cloned().map(|channel| {
+                move |reply: ConfirmationSender| Command::Subscribe(channel, reply)
+            }))
+            .await?;
 
-        Ok(())
+        let mut first_err = None;
+        for (channel, outcome) in channels.into_iter().zip(outcomes) {
+            match outcome {
+                Ok(()) => {
+                    if !self.channels.contains(&channel) {
+                        self.channels.push(channel);
+                    }
+                }
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+
+        first_err.map_or(Ok(()), Err)
     }
 
+    /// Unsubscribes from every channel in `channels`, concurrently, with the same
+    /// per-channel outcome tracking as [`Self::subscribe`].
     pub async fn unsubscribe(&mut self, channels: &[WsChannel]) -> Result<(), WsError> {
         for channel in channels.iter() {
             if !self.channels.contains(channel) {
@@ -112,173 +217,656 @@ impl FtxWebsocket {
             }
         }
 
-        self.subscribe_or_unsubscribe(channels, false).await?;
-        self.channels.retain(|c| !channels.contains(c));
+        let channels = channels.to_vec();
+        let outcomes = self
+            .send_commands(channels.iter().cloned().map(|channel| {
+                move |reply: ConfirmationSender| Command::Unsubscribe(channel, reply)
+            }))
+            .await?;
 
-        Ok(())
+        let mut first_err = None;
+        for (channel, outcome) in channels.into_iter().zip(outcomes) {
+            match outcome {
+                Ok(()) => self.channels.retain(|c| c != &channel),
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+
+        first_err.map_or(Ok(()), Err)
     }
 
     pub async fn unsubscribe_all(&mut self) -> Result<(), WsError> {
         let channels = self.channels.clone();
         self.unsubscribe(&channels).await?;
 
-        self.channels.clear();
-
         Ok(())
     }
 
-    async fn subscribe_or_unsubscribe(
+    /// Sends every command produced by `to_commands` to the actor before awaiting any
+    /// of their confirmations, so a batch of subscribe/unsubscribe calls is in flight
+    /// concurrently instead of waiting for each ack before sending the next command.
+    /// Returns one outcome per command, in the same order as `to_commands`, so a
+    /// caller can tell which channels in the batch actually succeeded instead of
+    /// treating one failure as a failure of the whole batch.
+    async fn send_commands(
         &mut self,
-        channels: &[WsChannel],
-        subscribe: bool,
-    ) -> Result<(), WsError> {
-        let op = if subscribe {
-            "subscribe"
-        } else {
-            "unsubscribe"
-        };
+        to_commands: impl IntoIterator<Item = impl FnOnce(ConfirmationSender) -> Command>,
+    ) -> Result<Vec<Result<(), WsError>>, WsError> {
+        let mut replies = Vec::new();
+        for to_command in to_commands {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.cmd_tx
+                .send(to_command(reply_tx))
+                .map_err(|_| WsError::ActorShutDown)?;
+            replies.push(reply_rx);
+        }
 
-        'channels: for channel in channels {
-            let (channel, symbol) = match channel {
-                WsChannel::Orderbook(symbol) => ("orderbook", symbol.as_str()),
-                WsChannel::Trades(symbol) => ("trades", symbol.as_str()),
-                WsChannel::Ticker(symbol) => ("ticker", symbol.as_str()),
-                WsChannel::Fills => ("fills", ""),
-                WsChannel::Orders => ("orders", ""),
-            };
-
-            self.stream
-                .send(Message::Text(
-                    json!({
-                        "op": op,
-                        "channel": channel,
-                        "market": symbol,
-                    })
-                    .to_string(),
-                ))
-                .await?;
-
-            // Confirmation should arrive within the next 100 updates
-            for _ in 0..100 {
-                let response = self.next_response().await?;
-                match response {
-                    WsResponse {
-                        r#type: WsMessageType::Subscribed,
-                        ..
-                    } if subscribe => {
-                        continue 'channels;
-                    }
-                    WsResponse {
-                        r#type: WsMessageType::Unsubscribed,
-                        ..
-                    } if !subscribe => {
-                        continue 'channels;
-                    }
-                    _ => {
-                        self.add_to_buffer(response);
-                    }
+        let outcomes = futures::future::join_all(
+            replies
+                .into_iter()
+                .map(|reply_rx| timer::timeout(SUBSCRIPTION_CONFIRMATION_TIMEOUT, reply_rx)),
+        )
+        .await;
+
+        Ok(outcomes
+            .into_iter()
+            .map(|outcome| match outcome {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) => Err(WsError::ActorShutDown),
+                Err(_) => Err(WsError::MissingSubscriptionConfirmation),
+            })
+            .collect())
+    }
+
+    pub fn run(self) {
+        rt::spawn(event_loop(self));
+    }
+}
+
+async fn event_loop(mut ws: FtxWebsocket) {
+    while let Some((symbol, event)) = ws.next().await {
+        if let Some(ref handler) = ws.event_handler {
+            handler.on_event(event, symbol);
+        }
+    }
+}
+
+impl Stream for FtxWebsocket {
+    type Item = (Option<Symbol>, EventData);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.data_rx).poll_next(cx) {
+                Poll::Ready(Some(Ok(item))) => return Poll::Ready(Some(item)),
+                // A slow consumer fell behind and missed some events; skip past the gap
+                // rather than surfacing the lag to callers.
+                Poll::Ready(Some(Err(_lagged))) => continue,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {
+                    // Nothing buffered right now. Only end the stream here, once
+                    // there's genuinely nothing left to drain — `data_rx` itself never
+                    // reports closed when the actor exits (see the comment on
+                    // `shutdown_rx`), so checking shutdown before data would risk
+                    // discarding an event the actor forwarded right before it died.
+                    return if Pin::new(&mut self.shutdown_rx).poll(cx).is_ready() {
+                        Poll::Ready(None)
+                    } else {
+                        Poll::Pending
+                    };
                 }
             }
-
-            return Err(WsError::MissingSubscriptionConfirmation);
         }
+    }
 
-        Ok(())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
+
+async fn login<S: WsTransport>(
+    stream: &mut S,
+    key: &str,
+    secret: &str,
+    subaccount: Option<String>,
+) -> Result<(), WsError> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let sign_payload = format!("{}websocket_login", timestamp);
+    let sign = HMAC::mac(sign_payload.as_bytes(), secret.as_bytes());
+    let sign = hex::encode(sign);
+
+    stream
+        .send_text(
+            json!({
+                "op": "login",
+                "args": {
+                    "key": key,
+                    "sign": sign,
+                    "time": timestamp as u64,
+                    "subaccount": subaccount,
+                }
+            })
+            .to_string(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn channel_op(channel: &WsChannel) -> (&'static str, &str) {
+    match channel {
+        WsChannel::Orderbook(symbol) => ("orderbook", symbol.as_str()),
+        WsChannel::Trades(symbol) => ("trades", symbol.as_str()),
+        WsChannel::Ticker(symbol) => ("ticker", symbol.as_str()),
+        WsChannel::Fills => ("fills", ""),
+        WsChannel::Orders => ("orders", ""),
     }
+}
+
+/// Owns the transport and is the only thing that ever reads or writes it. Runs as a
+/// standalone task (`tokio::spawn` natively, `wasm_bindgen_futures::spawn_local` on wasm)
+/// for the lifetime of the connection: it services `subscribe`/`unsubscribe` commands from
+/// the `FtxWebsocket` handle (completing the matching `oneshot` once the ack for that
+/// `(channel, market)` arrives), forwards decoded data frames out over `data_tx`, and
+/// answers the ping timer on its own.
+struct WsActor<C: WsConnector> {
+    connector: C,
+    stream: C::Socket,
+    ping_timer: PingTimer,
+    key: Option<String>,
+    secret: Option<String>,
+    subaccount: Option<String>,
+    auto_reconnect: bool,
+    track_order_books: bool,
+    channels: Vec<WsChannel>,
+    cmd_rx: mpsc::UnboundedReceiver<Command>,
+    data_tx: broadcast::Sender<(Option<Symbol>, EventData)>,
+    // The `Instant` is when the command was sent, so a stale entry (an `error`
+    // response with no channel/market to match it against, or an ack that just never
+    // arrives) can be swept out instead of leaking for the life of the connection.
+    pending: HashMap<(&'static str, String), (ConfirmationSender, Instant)>,
+    order_books: Arc<Mutex<HashMap<Symbol, OrderBook>>>,
+    // Never sent to; just held so that dropping it when `run` returns resolves the
+    // handle's `shutdown_rx` and lets `FtxWebsocket::poll_next` end the stream instead
+    // of pending forever on a `data_rx` that never reports closed.
+    #[allow(dead_code)]
+    _shutdown_tx: oneshot::Sender<()>,
+}
 
-    async fn next_response(&mut self) -> Result<WsResponse, WsError> {
+impl<C: WsConnector> WsActor<C> {
+    async fn run(mut self) {
+        // Once every `FtxWebsocket` handle (and thus every `cmd_tx`) is dropped,
+        // `cmd_rx.recv()` resolves to `Ready(None)` on every poll forever after, which
+        // would make that `select!` branch always ready and spin the task. Data-only
+        // consumers via `subscribe_events()` are still allowed to keep working, so
+        // instead of returning we just stop polling that branch.
+        let mut cmd_closed = false;
         loop {
             tokio::select! {
                 _ = self.ping_timer.tick() => {
-                    self.ping().await?;
+                    self.sweep_stale_pending();
+                    if self.ping().await.is_err() && !self.try_reconnect().await {
+                        return;
+                    }
                 },
-                Some(msg) = self.stream.next() => {
-                    let msg = msg?;
-                    if let Message::Text(text) = msg {
-                        let response: WsResponse = serde_json::from_str(&text)?;
-
-                        if let WsResponse { r#type: WsMessageType::Pong, .. } = response {
-                            continue;
+                cmd = self.cmd_rx.recv(), if !cmd_closed => {
+                    match cmd {
+                        Some(cmd) => {
+                            if self.handle_command(cmd).await.is_err() && !self.try_reconnect().await {
+                                return;
+                            }
                         }
-
-                        return Ok(response)
+                        None => cmd_closed = true,
                     }
                 },
+                msg = self.stream.next_text() => {
+                    match msg {
+                        Some(Ok(text)) => {
+                            if self.handle_message(text).await.is_err() {
+                                continue;
+                            }
+                        }
+                        _ => {
+                            if !self.try_reconnect().await {
+                                return;
+                            }
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    async fn ping(&mut self) -> Result<(), WsError> {
+        self.stream
+            .send_text(json!({ "op": "ping" }).to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Fails and drops any pending confirmation older than
+    /// `SUBSCRIPTION_CONFIRMATION_TIMEOUT`. Normally an ack removes its entry from
+    /// `pending`, but an `error` response (FTX doesn't echo back a channel/market to
+    /// match against) or a dropped ack would otherwise leak it for the life of the
+    /// connection.
+    fn sweep_stale_pending(&mut self) {
+        let stale: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, sent_at))| sent_at.elapsed() >= SUBSCRIPTION_CONFIRMATION_TIMEOUT)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in stale {
+            if let Some((reply, _)) = self.pending.remove(&key) {
+                let _ = reply.send(Err(WsError::MissingSubscriptionConfirmation));
+            }
+        }
+    }
+
+    async fn handle_command(&mut self, cmd: Command) -> Result<(), WsError> {
+        let (channel, reply, subscribe) = match cmd {
+            Command::Subscribe(channel, reply) => (channel, reply, true),
+            Command::Unsubscribe(channel, reply) => (channel, reply, false),
+        };
+
+        let op = if subscribe { "subscribe" } else { "unsubscribe" };
+        let (channel_name, market) = channel_op(&channel);
+
+        self.stream
+            .send_text(
+                json!({
+                    "op": op,
+                    "channel": channel_name,
+                    "market": market,
+                })
+                .to_string(),
+            )
+            .await?;
+
+        self.pending
+            .insert((channel_name, market.to_string()), (reply, Instant::now()));
+
+        if subscribe {
+            // A caller retrying a timed-out/failed `subscribe` call re-sends the same
+            // channel; without this check it'd pile up duplicate entries here and the
+            // reconnect replay would send duplicate `subscribe` ops to FTX.
+            if !self.channels.contains(&channel) {
+                self.channels.push(channel);
             }
+        } else {
+            self.channels.retain(|c| c != &channel);
         }
+
+        Ok(())
     }
 
-    fn add_to_buffer(&mut self, response: WsResponse) {
+    async fn handle_message(&mut self, text: String) -> Result<(), WsError> {
+        let response: WsResponse = serde_json::from_str(&text)?;
+
+        match response.r#type {
+            WsMessageType::Pong => {}
+            WsMessageType::Subscribed | WsMessageType::Unsubscribed => {
+                if let Some(channel) = response.channel.as_deref() {
+                    let market = response.market.clone().unwrap_or_default();
+                    if let Some((reply, _)) =
+                        self.pending.remove(&(channel_name_key(channel), market))
+                    {
+                        let _ = reply.send(Ok(()));
+                    }
+                }
+            }
+            _ => self.forward(response).await,
+        }
+
+        Ok(())
+    }
+
+    async fn forward(&mut self, response: WsResponse) {
+        let market = response.market;
         if let Some(data) = response.data {
+            if self.track_order_books {
+                if let (WsResponseData::OrderbookData(ref orderbook), Some(symbol)) =
+                    (&data, market.as_ref())
+                {
+                    self.update_order_book(symbol.clone(), orderbook).await;
+                }
+            }
+
             match data {
                 WsResponseData::Trades(trades) => {
                     for trade in trades {
-                        self.buf
-                            .push_back((response.market.clone(), EventData::Trade(trade)));
+                        let _ = self
+                            .data_tx
+                            .send((market.clone(), EventData::Trade(trade)));
                     }
                 }
                 WsResponseData::OrderbookData(orderbook) => {
-                    self.buf
-                        .push_back((response.market, EventData::OrderbookData(orderbook)));
+                    let _ = self
+                        .data_tx
+                        .send((market, EventData::OrderbookData(orderbook)));
                 }
                 WsResponseData::Fill(fill) => {
-                    self.buf.push_back((response.market, EventData::Fill(fill)));
+                    let _ = self.data_tx.send((market, EventData::Fill(fill)));
                 }
                 WsResponseData::Ticker(ticker) => {
-                    self.buf
-                        .push_back((response.market, EventData::Ticker(ticker)));
+                    let _ = self.data_tx.send((market, EventData::Ticker(ticker)));
                 }
                 WsResponseData::Order(order) => {
-                    self.buf
-                        .push_back((response.market, EventData::Order(order)));
+                    let _ = self.data_tx.send((market, EventData::Order(order)));
                 }
             }
         }
     }
 
-    pub fn run(self) {
-        tokio::spawn(event_loop(self));
+    /// Applies `data` to the locally-maintained book for `symbol` and, on a checksum
+    /// mismatch, emits `EventData::OrderbookDesync` and resubscribes to force a fresh
+    /// snapshot.
+    async fn update_order_book(&mut self, symbol: Symbol, data: &OrderbookData) {
+        let matches = {
+            let mut books = self.order_books.lock().unwrap();
+            if data.action == "partial" {
+                books.entry(symbol.clone()).or_default().apply_partial(data)
+            } else if let Some(book) = books.get_mut(&symbol) {
+                book.apply_update(data)
+            } else {
+                // No snapshot to apply the deltas to (none yet, or dropped by a resubscribe).
+                return;
+            }
+        };
+
+        if !matches {
+            let _ = self
+                .data_tx
+                .send((Some(symbol.clone()), EventData::OrderbookDesync));
+            self.resubscribe_orderbook(&symbol).await;
+        }
     }
-}
 
-async fn event_loop(mut ws: FtxWebsocket) {
-    loop {
-        let (symbol, event) = ws.next().await.expect("No data received").unwrap();
+    async fn resubscribe_orderbook(&mut self, symbol: &Symbol) {
+        let channel = WsChannel::Orderbook(symbol.clone());
+        let (channel_name, market) = channel_op(&channel);
 
-        if let Some(ref handler) = ws.event_handler {
-            handler.on_event(event, symbol);
+        let _ = self
+            .stream
+            .send_text(
+                json!({ "op": "unsubscribe", "channel": channel_name, "market": market })
+                    .to_string(),
+            )
+            .await;
+        let _ = self
+            .stream
+            .send_text(
+                json!({ "op": "subscribe", "channel": channel_name, "market": market })
+                    .to_string(),
+            )
+            .await;
+
+        self.order_books.lock().unwrap().remove(symbol);
+    }
+
+    /// Opens a fresh transport and, if this actor was previously authenticated,
+    /// re-logs in over it. A login failure fails the whole attempt (dropping the new,
+    /// unauthenticated transport) rather than leaving the actor connected but silently
+    /// unauthenticated, so `try_reconnect`'s backoff loop retries it like any other
+    /// connect failure instead of giving up on login after a single try.
+    async fn reconnect_and_login(&self) -> Result<C::Socket, WsError> {
+        let mut stream = self.connector.connect().await?;
+
+        if let (Some(key), Some(secret)) = (&self.key, &self.secret) {
+            login(&mut stream, key, secret, self.subaccount.clone()).await?;
         }
+
+        Ok(stream)
+    }
+
+    /// Reconnects with exponential backoff, re-authenticates if this actor was previously
+    /// authenticated, and replays every currently-subscribed channel. Returns `false` when
+    /// `auto_reconnect` is disabled, which tells `run` to wind the actor down.
+    async fn try_reconnect(&mut self) -> bool {
+        if !self.auto_reconnect {
+            return false;
+        }
+
+        // The old transport is gone, so none of these acks are ever coming; fail them
+        // now instead of leaving them for `sweep_stale_pending` to time out.
+        for (_, (reply, _)) in self.pending.drain() {
+            let _ = reply.send(Err(WsError::ActorShutDown));
+        }
+
+        let mut backoff = INITIAL_RECONNECT_BACKOFF;
+        self.stream = loop {
+            match self.reconnect_and_login().await {
+                Ok(stream) => break stream,
+                Err(_) => {
+                    let jitter_ms = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.subsec_millis() % 250)
+                        .unwrap_or(0);
+                    timer::sleep(backoff + Duration::from_millis(jitter_ms as u64)).await;
+                    backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                }
+            }
+        };
+
+        let channels = std::mem::take(&mut self.channels);
+        for channel in channels {
+            let (channel_name, market) = channel_op(&channel);
+            let sent = self
+                .stream
+                .send_text(
+                    json!({
+                        "op": "subscribe",
+                        "channel": channel_name,
+                        "market": market,
+                    })
+                    .to_string(),
+                )
+                .await;
+            if sent.is_ok() {
+                self.channels.push(channel);
+            }
+        }
+
+        let _ = self
+            .data_tx
+            .send((None, EventData::Reconnected));
+
+        true
     }
 }
 
-impl Stream for FtxWebsocket {
-    type Item = Result<(Option<Symbol>, EventData), WsError>;
+fn channel_name_key(channel: &str) -> &'static str {
+    match channel {
+        "orderbook" => "orderbook",
+        "trades" => "trades",
+        "ticker" => "ticker",
+        "fills" => "fills",
+        "orders" => "orders",
+        _ => "",
+    }
+}
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        loop {
-            if let Some(data) = self.buf.pop_front() {
-                return Poll::Ready(Some(Ok(data)));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+    use std::sync::Mutex as StdMutex;
+
+    /// An in-memory [`WsTransport`]: `send_text` records every frame the actor sends,
+    /// and `next_text` yields whatever the test pushes through the paired
+    /// `UnboundedSender` (dropping it simulates the connection closing).
+    struct FakeTransport {
+        incoming: mpsc::UnboundedReceiver<String>,
+        sent: Arc<StdMutex<Vec<String>>>,
+    }
+
+    #[cfg_attr(feature = "native", async_trait)]
+    #[cfg_attr(feature = "wasm", async_trait(?Send))]
+    impl WsTransport for FakeTransport {
+        async fn send_text(&mut self, text: String) -> Result<(), WsError> {
+            self.sent.lock().unwrap().push(text);
+            Ok(())
+        }
+
+        async fn next_text(&mut self) -> Option<Result<String, WsError>> {
+            self.incoming.recv().await.map(Ok)
+        }
+    }
+
+    fn fake_transport() -> (FakeTransport, mpsc::UnboundedSender<String>, Arc<StdMutex<Vec<String>>>) {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let sent = Arc::new(StdMutex::new(Vec::new()));
+        let transport = FakeTransport {
+            incoming: incoming_rx,
+            sent: sent.clone(),
+        };
+        (transport, incoming_tx, sent)
+    }
+
+    /// A [`WsConnector`] that hands out pre-built fake transports in order, so a test
+    /// can script what `WsActor` connects to across a reconnect without touching the
+    /// network.
+    struct FakeConnector {
+        queued: StdMutex<VecDeque<FakeTransport>>,
+    }
+
+    impl FakeConnector {
+        fn new(queued: Vec<FakeTransport>) -> Self {
+            Self {
+                queued: StdMutex::new(queued.into_iter().collect()),
             }
+        }
+    }
 
-            // Fetch new response if buffer is empty
-            let response = {
-                // safety: this is ok because the future from self.next_response() will only live in this function.
-                // It won't be moved anymore.
-                let mut next_response = self.next_response();
-                let pinned = unsafe { Pin::new_unchecked(&mut next_response) };
-                match ready!(pinned.poll(cx)) {
-                    Ok(response) => response,
-                    Err(e) => {
-                        return Poll::Ready(Some(Err(e)));
-                    }
-                }
-            };
+    #[cfg_attr(feature = "native", async_trait)]
+    #[cfg_attr(feature = "wasm", async_trait(?Send))]
+    impl WsConnector for FakeConnector {
+        type Socket = FakeTransport;
+
+        async fn connect(&self) -> Result<FakeTransport, WsError> {
+            self.queued
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| WsError::Transport("no fake transport queued".to_string()))
+        }
+    }
 
-            self.add_to_buffer(response);
+    /// Wires up a `FtxWebsocket` handle backed by a `WsActor<FakeConnector>` instead of
+    /// a real connection: `first` is what the actor starts on, `reconnects` is what it
+    /// connects to on each subsequent `try_reconnect`, in order.
+    fn spawn_handle(
+        first: FakeTransport,
+        reconnects: Vec<FakeTransport>,
+        auto_reconnect: bool,
+    ) -> FtxWebsocket {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (data_tx, data_rx) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let order_books = Arc::new(Mutex::new(HashMap::new()));
+
+        let actor = WsActor {
+            connector: FakeConnector::new(reconnects),
+            stream: first,
+            // Long enough that the interval's first (immediate) tick is the only ping
+            // sent during a test; real traffic only ever comes from the commands and
+            // acks the test drives explicitly.
+            ping_timer: PingTimer::new(Duration::from_secs(3600)),
+            key: None,
+            secret: None,
+            subaccount: None,
+            auto_reconnect,
+            track_order_books: false,
+            channels: Vec::new(),
+            cmd_rx,
+            data_tx: data_tx.clone(),
+            pending: HashMap::new(),
+            order_books: order_books.clone(),
+            _shutdown_tx: shutdown_tx,
+        };
+        tokio::spawn(actor.run());
+
+        FtxWebsocket {
+            channels: Vec::new(),
+            is_authenticated: true,
+            cmd_tx,
+            data_rx: BroadcastStream::new(data_rx),
+            data_tx,
+            shutdown_rx,
+            event_handler: None,
+            order_books,
         }
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.buf.len(), None)
+    #[tokio::test]
+    async fn subscribe_completes_once_the_actor_forwards_the_ack() {
+        let (transport, incoming_tx, sent) = fake_transport();
+        let mut handle = spawn_handle(transport, vec![], false);
+
+        let channel = WsChannel::Trades("BTC/USD".to_string());
+        let subscribe_fut = handle.subscribe(std::slice::from_ref(&channel));
+        let ack_fut = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            incoming_tx
+                .send(r#"{"type":"subscribed","channel":"trades","market":"BTC/USD"}"#.to_string())
+                .unwrap();
+        };
+
+        let (result, _) = tokio::join!(subscribe_fut, ack_fut);
+
+        assert!(result.is_ok());
+        assert_eq!(handle.channels, vec![channel]);
+        assert!(sent
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|frame| frame.contains("\"subscribe\"") && frame.contains("BTC/USD")));
+    }
+
+    #[tokio::test]
+    async fn partial_batch_failure_keeps_only_the_acked_channel_and_replays_on_reconnect() {
+        let (first, first_incoming_tx, _first_sent) = fake_transport();
+        let (second, _second_incoming_tx, second_sent) = fake_transport();
+        let mut handle = spawn_handle(first, vec![second], true);
+        let mut reconnected = handle.subscribe_events();
+
+        let acked = WsChannel::Trades("BTC/USD".to_string());
+        let never_acked = WsChannel::Trades("ETH/USD".to_string());
+
+        let subscribe_fut = handle.subscribe(&[acked.clone(), never_acked.clone()]);
+        let drive_fut = async {
+            // Let both subscribe commands reach the (still-live) first transport, ack
+            // only one of them...
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            first_incoming_tx
+                .send(r#"{"type":"subscribed","channel":"trades","market":"BTC/USD"}"#.to_string())
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            // ...then drop the connection. The still-pending `never_acked`
+            // confirmation should fail instead of leaking, while `acked`'s result
+            // (already delivered above) is unaffected.
+            drop(first_incoming_tx);
+        };
+
+        let (result, _) = tokio::join!(subscribe_fut, drive_fut);
+
+        assert!(matches!(result, Err(WsError::ActorShutDown)));
+        // Only the channel that was actually acked is recorded — a partial batch
+        // failure must not make the handle forget what did succeed.
+        assert_eq!(handle.channels, vec![acked]);
+
+        match reconnected.recv().await {
+            Ok((_, EventData::Reconnected)) => {}
+            other => panic!("expected a Reconnected event, got {other:?}"),
+        }
+
+        // The actor's own replay list tracks every channel it was asked to subscribe
+        // to (confirmed or not), so reconnecting resubscribes both.
+        let sent = second_sent.lock().unwrap();
+        assert!(sent.iter().any(|frame| frame.contains("BTC/USD")));
+        assert!(sent.iter().any(|frame| frame.contains("ETH/USD")));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,14 @@
+pub mod error;
+pub mod event_handler;
+mod ftx_websocket;
+pub mod models;
+pub mod orderbook;
+mod rt;
+mod timer;
+mod transport;
+
+pub use error::WsError;
+pub use event_handler::EventHandler;
+pub use ftx_websocket::FtxWebsocket;
+pub use models::*;
+pub use orderbook::OrderBook;
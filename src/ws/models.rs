@@ -0,0 +1,144 @@
+use serde::{Deserialize, Deserializer};
+
+pub type Symbol = String;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WsChannel {
+    Orderbook(Symbol),
+    Trades(Symbol),
+    Ticker(Symbol),
+    Fills,
+    Orders,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WsMessageType {
+    Error,
+    Subscribed,
+    Unsubscribed,
+    Info,
+    Pong,
+    Partial,
+    Update,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Trade {
+    pub id: u64,
+    pub price: f64,
+    pub size: f64,
+    pub side: String,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OrderbookData {
+    pub action: String,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+    pub checksum: u32,
+    pub time: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fill {
+    pub id: u64,
+    pub market: Symbol,
+    pub price: f64,
+    pub size: f64,
+    pub side: String,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Ticker {
+    pub bid: f64,
+    pub ask: f64,
+    pub last: f64,
+    pub time: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Order {
+    pub id: u64,
+    pub market: Symbol,
+    pub status: String,
+    pub filled_size: f64,
+    pub remaining_size: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum WsResponseData {
+    Trades(Vec<Trade>),
+    OrderbookData(OrderbookData),
+    Fill(Fill),
+    Ticker(Ticker),
+    Order(Order),
+}
+
+#[derive(Debug, Clone)]
+pub struct WsResponse {
+    pub r#type: WsMessageType,
+    pub channel: Option<String>,
+    pub market: Option<Symbol>,
+    pub data: Option<WsResponseData>,
+}
+
+impl<'de> Deserialize<'de> for WsResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            r#type: WsMessageType,
+            channel: Option<String>,
+            market: Option<Symbol>,
+            data: Option<serde_json::Value>,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let data = match (raw.channel.as_deref(), raw.data) {
+            (_, None) => None,
+            (Some("trades"), Some(data)) => Some(WsResponseData::Trades(
+                serde_json::from_value(data).map_err(serde::de::Error::custom)?,
+            )),
+            (Some("orderbook"), Some(data)) => Some(WsResponseData::OrderbookData(
+                serde_json::from_value(data).map_err(serde::de::Error::custom)?,
+            )),
+            (Some("fills"), Some(data)) => Some(WsResponseData::Fill(
+                serde_json::from_value(data).map_err(serde::de::Error::custom)?,
+            )),
+            (Some("ticker"), Some(data)) => Some(WsResponseData::Ticker(
+                serde_json::from_value(data).map_err(serde::de::Error::custom)?,
+            )),
+            (Some("orders"), Some(data)) => Some(WsResponseData::Order(
+                serde_json::from_value(data).map_err(serde::de::Error::custom)?,
+            )),
+            (_, Some(_)) => None,
+        };
+
+        Ok(WsResponse {
+            r#type: raw.r#type,
+            channel: raw.channel,
+            market: raw.market,
+            data,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EventData {
+    Trade(Trade),
+    OrderbookData(OrderbookData),
+    Fill(Fill),
+    Ticker(Ticker),
+    Order(Order),
+    /// Emitted once the socket has re-established its connection (and, if applicable,
+    /// re-authenticated and resubscribed) after an unexpected disconnect.
+    Reconnected,
+    /// A locally-maintained order book (see [`super::orderbook::OrderBook`]) failed its
+    /// checksum against FTX and a fresh snapshot has been requested via resubscribe.
+    OrderbookDesync,
+}
@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use super::models::OrderbookData;
+
+/// How many price levels on each side feed into FTX's checksum.
+const CHECKSUM_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).expect("price is never NaN")
+    }
+}
+
+/// A locally-reconstructed order book for a single market: a `partial` snapshot resets
+/// it, and `update` frames apply price -> size deltas (a size of `0` removes the level).
+/// After every apply the top `CHECKSUM_DEPTH` levels are checked against FTX's CRC32
+/// checksum so a consumer can tell a desynced book from a merely-thin one.
+#[derive(Debug, Default, Clone)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resets the book to the given `partial` snapshot and reports whether it matches
+    /// the snapshot's checksum.
+    pub fn apply_partial(&mut self, data: &OrderbookData) -> bool {
+        self.bids.clear();
+        self.asks.clear();
+        self.apply_levels(data)
+    }
+
+    /// Applies an `update` frame's price -> size deltas and reports whether the book
+    /// still matches the frame's checksum afterwards.
+    pub fn apply_update(&mut self, data: &OrderbookData) -> bool {
+        self.apply_levels(data)
+    }
+
+    fn apply_levels(&mut self, data: &OrderbookData) -> bool {
+        for &(price, size) in &data.bids {
+            if size == 0.0 {
+                self.bids.remove(&Price(price));
+            } else {
+                self.bids.insert(Price(price), size);
+            }
+        }
+        for &(price, size) in &data.asks {
+            if size == 0.0 {
+                self.asks.remove(&Price(price));
+            } else {
+                self.asks.insert(Price(price), size);
+            }
+        }
+
+        self.checksum() == data.checksum
+    }
+
+    /// `(price, size)` of the highest bid and lowest ask currently on the book.
+    pub fn best_bid_ask(&self) -> (Option<(f64, f64)>, Option<(f64, f64)>) {
+        let best_bid = self.bids.iter().next_back().map(|(p, &s)| (p.0, s));
+        let best_ask = self.asks.iter().next().map(|(p, &s)| (p.0, s));
+        (best_bid, best_ask)
+    }
+
+    /// Up to `depth` `(price, size)` levels on each side, bids highest-first and asks
+    /// lowest-first.
+    pub fn depth(&self, depth: usize) -> (Vec<(f64, f64)>, Vec<(f64, f64)>) {
+        let bids = self
+            .bids
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(p, &s)| (p.0, s))
+            .collect();
+        let asks = self
+            .asks
+            .iter()
+            .take(depth)
+            .map(|(p, &s)| (p.0, s))
+            .collect();
+        (bids, asks)
+    }
+
+    /// FTX's checksum: CRC32 over the top `CHECKSUM_DEPTH` bid/ask levels, interleaved
+    /// as `bidPrice:bidSize:askPrice:askSize:...` and joined by `:`.
+    fn checksum(&self) -> u32 {
+        let mut bids = self.bids.iter().rev().take(CHECKSUM_DEPTH);
+        let mut asks = self.asks.iter().take(CHECKSUM_DEPTH);
+
+        let mut parts = Vec::with_capacity(CHECKSUM_DEPTH * 4);
+        loop {
+            let bid = bids.next();
+            let ask = asks.next();
+            if bid.is_none() && ask.is_none() {
+                break;
+            }
+            if let Some((price, size)) = bid {
+                parts.push(format_checksum_number(price.0));
+                parts.push(format_checksum_number(*size));
+            }
+            if let Some((price, size)) = ask {
+                parts.push(format_checksum_number(price.0));
+                parts.push(format_checksum_number(*size));
+            }
+        }
+
+        crc32fast::hash(parts.join(":").as_bytes())
+    }
+}
+
+/// FTX's checksum strips trailing zeroes (and a trailing `.`) from each number instead
+/// of using the default float formatting.
+fn format_checksum_number(n: f64) -> String {
+    let mut s = format!("{:.10}", n);
+    while s.ends_with('0') {
+        s.pop();
+    }
+    if s.ends_with('.') {
+        s.pop();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, checksum: u32) -> OrderbookData {
+        OrderbookData {
+            action: "partial".to_string(),
+            bids,
+            asks,
+            checksum,
+            time: 0.0,
+        }
+    }
+
+    fn update(bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>, checksum: u32) -> OrderbookData {
+        OrderbookData {
+            action: "update".to_string(),
+            bids,
+            asks,
+            checksum,
+            time: 0.0,
+        }
+    }
+
+    // Checksum computed independently (CRC32 over
+    // "100:1:101:1.5:99.5:2:101.5:2.5") to avoid testing the implementation against
+    // itself.
+    #[test]
+    fn apply_partial_matches_known_checksum() {
+        let mut book = OrderBook::new();
+        let snapshot = partial(
+            vec![(100.0, 1.0), (99.5, 2.0)],
+            vec![(101.0, 1.5), (101.5, 2.5)],
+            1_519_281_254,
+        );
+
+        assert!(book.apply_partial(&snapshot));
+        assert_eq!(
+            book.best_bid_ask(),
+            (Some((100.0, 1.0)), Some((101.0, 1.5)))
+        );
+        assert_eq!(
+            book.depth(10),
+            (
+                vec![(100.0, 1.0), (99.5, 2.0)],
+                vec![(101.0, 1.5), (101.5, 2.5)]
+            )
+        );
+    }
+
+    // Checksum computed independently (CRC32 over "100:1.2:101:1.5:101.5:2.5") for the
+    // book left after removing the 99.5 bid level and resizing the top bid to 1.2.
+    #[test]
+    fn apply_update_removes_and_resizes_levels() {
+        let mut book = OrderBook::new();
+        book.apply_partial(&partial(
+            vec![(100.0, 1.0), (99.5, 2.0)],
+            vec![(101.0, 1.5), (101.5, 2.5)],
+            1_519_281_254,
+        ));
+
+        let matches = book.apply_update(&update(
+            vec![(99.5, 0.0), (100.0, 1.2)],
+            vec![],
+            3_171_391_600,
+        ));
+
+        assert!(matches);
+        assert_eq!(book.best_bid_ask(), (Some((100.0, 1.2)), Some((101.0, 1.5))));
+    }
+
+    #[test]
+    fn apply_update_reports_mismatch_on_bad_checksum() {
+        let mut book = OrderBook::new();
+        book.apply_partial(&partial(
+            vec![(100.0, 1.0), (99.5, 2.0)],
+            vec![(101.0, 1.5), (101.5, 2.5)],
+            1_519_281_254,
+        ));
+
+        // Right diff, wrong checksum: this is what should make the caller (`WsActor`)
+        // emit `EventData::OrderbookDesync` and resubscribe for a fresh snapshot.
+        let matches = book.apply_update(&update(vec![(99.5, 0.0), (100.0, 1.2)], vec![], 0));
+
+        assert!(!matches);
+    }
+}
@@ -0,0 +1,14 @@
+//! Spawns a future onto whichever executor is available for the enabled transport
+//! feature: Tokio natively, `wasm_bindgen_futures` in the browser.
+
+use std::future::Future;
+
+#[cfg(feature = "native")]
+pub(crate) fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(fut);
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) fn spawn(fut: impl Future<Output = ()> + 'static) {
+    wasm_bindgen_futures::spawn_local(fut);
+}
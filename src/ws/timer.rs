@@ -0,0 +1,69 @@
+//! Wasm-compatible stand-in for `tokio::time::Interval`, since `tokio`'s timer driver
+//! isn't available on `wasm32-unknown-unknown`.
+
+use std::time::Duration;
+
+#[cfg(feature = "native")]
+pub(crate) struct PingTimer(tokio::time::Interval);
+
+#[cfg(feature = "native")]
+impl PingTimer {
+    pub(crate) fn new(period: Duration) -> Self {
+        Self(tokio::time::interval(period))
+    }
+
+    pub(crate) async fn tick(&mut self) {
+        self.0.tick().await;
+    }
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) struct PingTimer(Duration);
+
+#[cfg(feature = "wasm")]
+impl PingTimer {
+    pub(crate) fn new(period: Duration) -> Self {
+        Self(period)
+    }
+
+    pub(crate) async fn tick(&mut self) {
+        sleep(self.0).await;
+    }
+}
+
+#[cfg(feature = "native")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) async fn sleep(duration: Duration) {
+    gloo_timers::future::TimeoutFuture::new(duration.as_millis() as u32).await;
+}
+
+/// `Err(())` means `fut` didn't complete within `duration`, mirroring `tokio::time::timeout`
+/// without depending on Tokio's timer driver (unavailable on `wasm32-unknown-unknown`).
+#[cfg(feature = "native")]
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: Duration,
+    fut: F,
+) -> Result<F::Output, ()> {
+    tokio::time::timeout(duration, fut).await.map_err(|_| ())
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: Duration,
+    fut: F,
+) -> Result<F::Output, ()> {
+    use futures::future::{select, Either};
+
+    futures::pin_mut!(fut);
+    let timer = sleep(duration);
+    futures::pin_mut!(timer);
+
+    match select(fut, timer).await {
+        Either::Left((out, _)) => Ok(out),
+        Either::Right(_) => Err(()),
+    }
+}
@@ -0,0 +1,135 @@
+//! Socket abstraction that lets [`crate::ws::FtxWebsocket`] run unmodified on either a
+//! native Tokio runtime or `wasm32-unknown-unknown`. Mirrors the `if_wasm!`/`if_not_wasm!`
+//! split used by ethers-providers' WS transport: one small trait, two feature-gated
+//! implementations, picked at compile time.
+
+use async_trait::async_trait;
+
+use super::error::WsError;
+
+/// A text-framed websocket connection. The actor only ever needs to send a string and
+/// pull the next one back out, so that's all this trait exposes.
+#[cfg_attr(feature = "native", async_trait)]
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+pub(crate) trait WsTransport {
+    async fn send_text(&mut self, text: String) -> Result<(), WsError>;
+
+    /// `None` means the connection closed; `Some(Err(_))` is a transport-level error.
+    async fn next_text(&mut self) -> Option<Result<String, WsError>>;
+}
+
+#[cfg(feature = "native")]
+mod native {
+    use super::{async_trait, WsError, WsTransport};
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{
+        connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream,
+    };
+
+    pub(crate) struct NativeTransport(WebSocketStream<MaybeTlsStream<TcpStream>>);
+
+    pub(crate) async fn connect(url: &str) -> Result<NativeTransport, WsError> {
+        let (stream, _) = connect_async(url).await?;
+        Ok(NativeTransport(stream))
+    }
+
+    #[async_trait]
+    impl WsTransport for NativeTransport {
+        async fn send_text(&mut self, text: String) -> Result<(), WsError> {
+            self.0.send(Message::Text(text)).await?;
+            Ok(())
+        }
+
+        async fn next_text(&mut self) -> Option<Result<String, WsError>> {
+            loop {
+                return match self.0.next().await? {
+                    Ok(Message::Text(text)) => Some(Ok(text)),
+                    Ok(_) => continue,
+                    Err(e) => Some(Err(e.into())),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod wasm {
+    use super::{async_trait, WsError, WsTransport};
+    use futures::{SinkExt, StreamExt};
+    use ws_stream_wasm::{WsMessage, WsMeta, WsStream};
+
+    pub(crate) struct WasmTransport(WsStream);
+
+    pub(crate) async fn connect(url: &str) -> Result<WasmTransport, WsError> {
+        let (_, stream) = WsMeta::connect(url, None)
+            .await
+            .map_err(|e| WsError::Transport(e.to_string()))?;
+        Ok(WasmTransport(stream))
+    }
+
+    #[async_trait(?Send)]
+    impl WsTransport for WasmTransport {
+        async fn send_text(&mut self, text: String) -> Result<(), WsError> {
+            self.0
+                .send(WsMessage::Text(text))
+                .await
+                .map_err(|e| WsError::Transport(e.to_string()))
+        }
+
+        async fn next_text(&mut self) -> Option<Result<String, WsError>> {
+            loop {
+                return match self.0.next().await? {
+                    WsMessage::Text(text) => Some(Ok(text)),
+                    WsMessage::Binary(_) => continue,
+                };
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "native", feature = "wasm"))]
+compile_error!("features \"native\" and \"wasm\" are mutually exclusive");
+
+#[cfg(not(any(feature = "native", feature = "wasm")))]
+compile_error!("one of the \"native\" or \"wasm\" features must be enabled");
+
+#[cfg(feature = "native")]
+pub(crate) type Transport = native::NativeTransport;
+#[cfg(feature = "wasm")]
+pub(crate) type Transport = wasm::WasmTransport;
+
+pub(crate) async fn connect(url: &str) -> Result<Transport, WsError> {
+    #[cfg(feature = "native")]
+    {
+        native::connect(url).await
+    }
+    #[cfg(feature = "wasm")]
+    {
+        wasm::connect(url).await
+    }
+}
+
+/// Produces a fresh connection for `WsActor`'s (re)connect logic. A trait, rather than
+/// `WsActor` calling `transport::connect` directly, so that logic can be exercised
+/// against a fake transport in tests without touching the network.
+#[cfg_attr(feature = "native", async_trait)]
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+pub(crate) trait WsConnector {
+    type Socket: WsTransport;
+
+    async fn connect(&self) -> Result<Self::Socket, WsError>;
+}
+
+/// The production [`WsConnector`]: opens a real connection to a fixed URL.
+pub(crate) struct EndpointConnector(pub(crate) &'static str);
+
+#[cfg_attr(feature = "native", async_trait)]
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+impl WsConnector for EndpointConnector {
+    type Socket = Transport;
+
+    async fn connect(&self) -> Result<Transport, WsError> {
+        connect(self.0).await
+    }
+}